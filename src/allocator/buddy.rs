@@ -0,0 +1,186 @@
+use super::Locked;
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cmp::max,
+    mem,
+};
+
+/// 伙伴系统所维护空闲链表的阶数
+///
+/// 第`k`阶的块大小为`MIN_BLOCK_SIZE << k`；最高一阶覆盖整个堆。
+const MAX_ORDER: usize = 32;
+
+/// 最小块大小
+///
+/// 必须足以存储一个`ListNode`，并且是2的幂，这样块地址才能按其自身大小对齐。
+const MIN_BLOCK_SIZE: usize = 16;
+
+/// 一个存储于空闲块内部的链表节点
+struct ListNode {
+    next: Option<&'static mut ListNode>,
+}
+
+/// 一个伙伴系统分配器
+///
+/// 把整个堆视为一整块，按2的幂拆分/合并伙伴块以满足分配请求，
+/// 从而在释放时能够与相邻的伙伴块重新合并，避免外部碎片不断累积。
+pub struct BuddyAllocator {
+    heap_start: usize,
+    free_lists: [Option<&'static mut ListNode>; MAX_ORDER],
+}
+
+impl BuddyAllocator {
+    /// 创建一个空的`BuddyAllocator`
+    pub const fn new() -> Self {
+        const EMPTY: Option<&'static mut ListNode> = None;
+        BuddyAllocator {
+            heap_start: 0,
+            free_lists: [EMPTY; MAX_ORDER],
+        }
+    }
+
+    /// 使用给定的堆边界初始化分配器
+    ///
+    /// 该方法为非安全，因为调用者必须保证提供的内存范围未被使用。
+    /// 同时，该方法只能被调用一次。若`heap_size`本身不是2的幂（例如这个
+    /// 内核100KiB的`HEAP_SIZE`），就把它分解成若干个2的幂大小的块——对应
+    /// `heap_size`二进制表示中每一个被置位的比特——逐块加入各自阶数的空闲
+    /// 链表，从最大的块开始分配地址。这保证了堆的每一个字节都可用，而不
+    /// 是像只取“不超过堆大小的最大2的幂块”那样，把剩下的部分永久丢弃。
+    /// 按从大到小的顺序给块分配地址，也保证了每个块的地址都按自身大小
+    /// 对齐，这是伙伴地址异或计算成立的前提。
+    pub unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        self.heap_start = heap_start;
+
+        let mut offset = 0usize;
+        let mut remaining = heap_size;
+        for order in (0..MAX_ORDER).rev() {
+            let block_size = MIN_BLOCK_SIZE << order;
+            if block_size <= remaining {
+                self.push_free_block(heap_start + offset, order);
+                offset += block_size;
+                remaining -= block_size;
+            }
+        }
+        // `remaining`此时小于`MIN_BLOCK_SIZE`，不足以构成任何一个可用的块，
+        // 只能被丢弃（这部分最多浪费`MIN_BLOCK_SIZE - 1`字节）。
+    }
+
+    /// 计算容纳`size`所需要的最小阶数
+    ///
+    /// 循环本身以`MAX_ORDER`为界：没有任何阶数能装下`size`时返回
+    /// `MAX_ORDER`，而不是无界地继续把`order`往上加——后者对于远超堆容量
+    /// 的`size`会一路把`MIN_BLOCK_SIZE << order`移位移到溢出。调用方
+    /// （`allocate`）本就会把`order >= MAX_ORDER`当作分配失败处理，这里只
+    /// 是让这个判断在进入那段逻辑之前就已经成立。
+    fn order_for_size(&self, size: usize) -> usize {
+        let size = max(size, MIN_BLOCK_SIZE);
+        let mut order = 0;
+        while order < MAX_ORDER && (MIN_BLOCK_SIZE << order) < size {
+            order += 1;
+        }
+        order
+    }
+
+    /// 将地址为`addr`的`order`阶块加入对应的空闲链表
+    unsafe fn push_free_block(&mut self, addr: usize, order: usize) {
+        let mut node = ListNode { next: None };
+        node.next = self.free_lists[order].take();
+        let node_ptr = addr as *mut ListNode;
+        node_ptr.write(node);
+        self.free_lists[order] = Some(&mut *node_ptr);
+    }
+
+    /// 从`order`阶的空闲链表中移除并返回地址为`addr`的块（若存在）
+    fn remove_free_block(&mut self, order: usize, addr: usize) -> bool {
+        let mut current = &mut self.free_lists[order];
+        loop {
+            match current {
+                None => return false,
+                Some(node) => {
+                    if (*node as *const ListNode as usize) == addr {
+                        *current = node.next.take();
+                        return true;
+                    }
+                    current = &mut current.as_mut().unwrap().next;
+                }
+            }
+        }
+    }
+
+    /// 从`order`阶的空闲链表中弹出一个块（若存在）
+    fn pop_free_block(&mut self, order: usize) -> Option<usize> {
+        let node = self.free_lists[order].take()?;
+        self.free_lists[order] = node.next.take();
+        Some(node as *mut ListNode as usize)
+    }
+
+    /// 计算`addr`处`order`阶块的伙伴地址
+    fn buddy_addr(&self, addr: usize, order: usize) -> usize {
+        let block_size = MIN_BLOCK_SIZE << order;
+        let offset = addr - self.heap_start;
+        self.heap_start + (offset ^ block_size)
+    }
+
+    /// 按给定的内存布局分配一块内存，失败时返回`None`
+    fn allocate(&mut self, layout: Layout) -> Option<*mut u8> {
+        let target_order = self.order_for_size(layout.size().max(layout.align()));
+
+        // 找到不低于目标阶数的最低非空阶数
+        let mut order = target_order;
+        while order < MAX_ORDER && self.free_lists[order].is_none() {
+            order += 1;
+        }
+        if order >= MAX_ORDER {
+            return None;
+        }
+
+        let addr = self.pop_free_block(order).unwrap();
+
+        // 不断对半拆分，把多出来的伙伴块放回更低阶的空闲链表，直到达到目标阶数
+        let mut current_order = order;
+        let mut current_addr = addr;
+        while current_order > target_order {
+            current_order -= 1;
+            let buddy = current_addr + (MIN_BLOCK_SIZE << current_order);
+            unsafe { self.push_free_block(buddy, current_order) };
+        }
+
+        Some(current_addr as *mut u8)
+    }
+
+    /// 释放一块内存，并在其伙伴块同为空闲时持续向上合并
+    ///
+    /// 该方法为非安全，因为调用者必须保证`ptr`指向一块通过`allocate`以同样布局分配的内存。
+    unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout) {
+        let mut order = self.order_for_size(layout.size().max(layout.align()));
+        let mut addr = ptr as usize;
+
+        while order < MAX_ORDER - 1 {
+            let buddy = self.buddy_addr(addr, order);
+            if self.remove_free_block(order, buddy) {
+                // 伙伴块空闲，合并为更高一阶的块并继续尝试向上合并
+                addr = addr.min(buddy);
+                order += 1;
+            } else {
+                break;
+            }
+        }
+
+        self.push_free_block(addr, order);
+    }
+}
+
+unsafe impl GlobalAlloc for Locked<BuddyAllocator> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let layout = layout.align_to(mem::align_of::<ListNode>()).unwrap();
+        self.lock()
+            .allocate(layout)
+            .unwrap_or(core::ptr::null_mut())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let layout = layout.align_to(mem::align_of::<ListNode>()).unwrap();
+        self.lock().deallocate(ptr, layout)
+    }
+}
@@ -0,0 +1,106 @@
+use super::{linked_list::LinkedListAllocator, Locked};
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    mem, ptr,
+};
+
+/// 一个存储于空闲块内部的链表节点
+struct ListNode {
+    next: Option<&'static mut ListNode>,
+}
+
+/// 分段空闲链表所使用的块大小
+///
+/// 这些数值必须都是2的幂，因为它们同时被用作所申请块的对齐方式。
+const BLOCK_SIZES: &[usize] = &[8, 16, 32, 64, 128, 256, 512, 1024, 2048];
+
+/// 为给定的内存布局选择一个合适的块大小
+///
+/// 返回`BLOCK_SIZES`中能够容纳该布局的最小块的下标。
+fn list_index(layout: &Layout) -> Option<usize> {
+    let required_block_size = layout.size().max(layout.align());
+    BLOCK_SIZES.iter().position(|&s| s >= required_block_size)
+}
+
+/// 一个固定大小的块分配器
+///
+/// 为每一种块大小维护一条独立的空闲链表，分配和释放都是O(1)的；
+/// 超出最大块大小（或对齐要求超出块大小）的请求交由`fallback_allocator`处理。
+pub struct FixedSizeBlockAllocator {
+    list_heads: [Option<&'static mut ListNode>; BLOCK_SIZES.len()],
+    fallback_allocator: LinkedListAllocator,
+}
+
+impl FixedSizeBlockAllocator {
+    /// 创建一个空的`FixedSizeBlockAllocator`
+    pub const fn new() -> Self {
+        const EMPTY: Option<&'static mut ListNode> = None;
+        FixedSizeBlockAllocator {
+            list_heads: [EMPTY; BLOCK_SIZES.len()],
+            fallback_allocator: LinkedListAllocator::new(),
+        }
+    }
+
+    /// 使用给定的堆边界初始化分配器
+    ///
+    /// 该方法为非安全，因为调用者必须保证提供的内存范围未被使用。
+    /// 同时，该方法只能被调用一次。
+    pub unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        self.fallback_allocator.init(heap_start, heap_size);
+    }
+
+    /// 在`fallback_allocator`上分配内存
+    ///
+    /// 用于处理没有对应空闲链表的分配请求。
+    fn fallback_alloc(&mut self, layout: Layout) -> *mut u8 {
+        self.fallback_allocator
+            .allocate(layout)
+            .unwrap_or(ptr::null_mut())
+    }
+}
+
+unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut allocator = self.lock();
+        match list_index(&layout) {
+            Some(index) => match allocator.list_heads[index].take() {
+                Some(node) => {
+                    allocator.list_heads[index] = node.next.take();
+                    node as *mut ListNode as *mut u8
+                }
+                None => {
+                    // 对应大小的空闲链表为空，从`fallback_allocator`申请一个新块
+                    // 块大小同时被用作对齐方式，因为所有块大小都是2的幂
+                    let block_size = BLOCK_SIZES[index];
+                    let block_align = block_size;
+                    match Layout::from_size_align(block_size, block_align) {
+                        Ok(layout) => allocator.fallback_alloc(layout),
+                        Err(_) => ptr::null_mut(),
+                    }
+                }
+            },
+            None => allocator.fallback_alloc(layout),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let mut allocator = self.lock();
+        match list_index(&layout) {
+            Some(index) => {
+                // 确保该块足以存储一个`ListNode`
+                assert!(mem::size_of::<ListNode>() <= BLOCK_SIZES[index]);
+                assert!(mem::align_of::<ListNode>() <= BLOCK_SIZES[index]);
+
+                let new_node = ListNode {
+                    next: allocator.list_heads[index].take(),
+                };
+                let new_node_ptr = ptr as *mut ListNode;
+                new_node_ptr.write(new_node);
+                allocator.list_heads[index] = Some(&mut *new_node_ptr);
+            }
+            None => {
+                allocator.fallback_allocator.deallocate(ptr, layout);
+            }
+        }
+    }
+}
@@ -40,18 +40,60 @@ impl LinkedListAllocator {
     pub unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
         self.add_free_region(heap_start, heap_size);
     }
-    /// 将给定的内存区域添加至链表前端
+    /// 将给定的内存区域按起始地址插入链表，并与物理相邻的空闲区域合并
+    ///
+    /// 链表始终按地址升序排列且不存在两个互相物理相邻的节点：若新区域紧邻
+    /// 前驱节点的末尾，则直接扩展前驱节点；若新区域（或扩展后的前驱节点）
+    /// 紧邻后继节点的起始地址，则把后继节点吸收进来。这保证了长时间运行后
+    /// 堆不会碎片化成大量无法满足分配的小节点。
     unsafe fn add_free_region(&mut self, addr: usize, size: usize) {
         // 确保此空闲区域足以容纳一个`ListNode`
         assert_eq!(align_up(addr, mem::align_of::<ListNode>()), addr);
         assert!(size >= mem::size_of::<ListNode>());
 
-        // 创建一个新的`ListNode`并将其添加至链表前端
-        let mut node = ListNode::new(size);
-        node.next = self.head.next.take();
-        let node_ptr = addr as *mut ListNode;
-        node_ptr.write(node);
-        self.head.next = Some(&mut *node_ptr)
+        let mut addr = addr;
+        let mut size = size;
+
+        // 找到插入点：`current`是地址上紧邻在新区域之前的节点（可能是哑头节点）
+        let mut current = &mut self.head;
+        while let Some(ref next) = current.next {
+            if next.start_addr() >= addr {
+                break;
+            }
+            current = current.next.as_mut().unwrap();
+        }
+
+        // 若`current`是真实节点且与新区域物理相邻，则扩展`current`而不是插入新节点
+        let merges_prev = current.size > 0 && current.end_addr() == addr;
+        if merges_prev {
+            current.size += size;
+            addr = current.start_addr();
+            size = current.size;
+        } else {
+            let mut node = ListNode::new(size);
+            node.next = current.next.take();
+            let node_ptr = addr as *mut ListNode;
+            node_ptr.write(node);
+            current.next = Some(&mut *node_ptr);
+        }
+
+        // `region`代表新区域所在的节点：要么是刚刚扩展的前驱节点，要么是新插入的节点。
+        // 检查它是否与后继节点物理相邻，相邻则把后继节点吸收进来。
+        let region = if merges_prev {
+            current
+        } else {
+            current.next.as_mut().unwrap()
+        };
+
+        let merges_next = region
+            .next
+            .as_ref()
+            .map_or(false, |next| next.start_addr() == addr + size);
+        if merges_next {
+            let next_node = region.next.take().unwrap();
+            region.size = size + next_node.size;
+            region.next = next_node.next;
+        }
     }
 
     fn find_region(&mut self, size: usize, align: usize) -> Option<(&'static mut ListNode, usize)> {
@@ -108,27 +150,39 @@ impl LinkedListAllocator {
         let size = layout.size().max(mem::size_of::<ListNode>());
         (size, layout.align())
     }
+
+    /// 按给定的内存布局进行分配，失败时返回`None`
+    ///
+    /// 与`GlobalAlloc::alloc`逻辑相同，供`fixed_size_block`分配器作为后备分配器复用。
+    pub(super) fn allocate(&mut self, layout: Layout) -> Option<*mut u8> {
+        let (size, align) = Self::size_align(layout);
+
+        let (region, alloc_start) = self.find_region(size, align)?;
+        let alloc_end = alloc_start.checked_add(size).expect("overflow");
+        let excess_size = region.end_addr() - alloc_end;
+        if excess_size > 0 {
+            unsafe { self.add_free_region(alloc_end, excess_size) };
+        }
+        Some(alloc_start as *mut u8)
+    }
+
+    /// 按给定的内存布局释放一块内存
+    ///
+    /// 与`GlobalAlloc::dealloc`逻辑相同，供`fixed_size_block`分配器作为后备分配器复用。
+    ///
+    /// 该方法为非安全，因为调用者必须保证`ptr`指向一块通过`allocate`以同样布局分配的内存。
+    pub(super) unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout) {
+        let (size, _) = Self::size_align(layout);
+        self.add_free_region(ptr as usize, size);
+    }
 }
 
 unsafe impl GlobalAlloc for Locked<LinkedListAllocator> {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        let (size, align) = LinkedListAllocator::size_align(layout);
-        let mut allocator = self.lock();
-
-        if let Some((region, alloc_start)) = allocator.find_region(size, align) {
-            let alloc_end = alloc_start.checked_add(size).expect("overflow");
-            let excess_size = region.end_addr() - alloc_end;
-            if excess_size > 0 {
-                allocator.add_free_region(alloc_end, excess_size);
-            }
-            alloc_start as *mut u8
-        } else {
-            ptr::null_mut()
-        }
+        self.lock().allocate(layout).unwrap_or(ptr::null_mut())
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        let (size, _) = LinkedListAllocator::size_align(layout);
-        self.lock().add_free_region(ptr as usize, size);
+        self.lock().deallocate(ptr, layout)
     }
 }
@@ -10,6 +10,7 @@ use bootloader::{entry_point, BootInfo};
 use os_rust_demo::task::executor::Executor;
 use core::panic::PanicInfo;
 use os_rust_demo::allocator::init_heap;
+use os_rust_demo::process::Process;
 use os_rust_demo::task::simple_executor::SimpleExecutor;
 use os_rust_demo::task::{keyboard, Task};
 use os_rust_demo::{
@@ -18,7 +19,7 @@ use os_rust_demo::{
     println,
 };
 use x86_64::{
-    structures::paging::{Page, Translate},
+    structures::paging::{OffsetPageTable, Page, Translate},
     VirtAddr,
 };
 
@@ -53,13 +54,16 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
 
     let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
     let mut mapper = unsafe { memory::init(phys_mem_offset) };
-    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+    let mut frame_allocator =
+        unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map, phys_mem_offset) };
 
     allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
 
     #[cfg(test)]
     test_main();
 
+    run_process_subsystem_self_check(&mapper, phys_mem_offset, &mut frame_allocator);
+
     let mut executor = Executor::new();
     executor.spawn(Task::new(example_task()));
     executor.spawn(Task::new(keyboard::print_keypresses()));
@@ -75,6 +79,81 @@ async fn example_task() {
     println!("async number: {}", number);
 }
 
+/// 加载一个自检用的ELF映像，验证`process`子系统确实能把它映射进一个全新
+/// 的地址空间
+///
+/// 只调用`Process::from_elf`，不调用`spawn_user`：后者需要GDT里装好的
+/// 用户态段选择子（`USER_CODE_SELECTOR`/`USER_DATA_SELECTOR`），而这个
+/// 内核还没有对应的GDT设置，真的`iretq`过去会立刻三重故障。所以这里只
+/// 验证“解析ELF、分配页表、映射`PT_LOAD`段和用户栈”这条加载路径本身是
+/// 正确的，跳到ring 3执行留给GDT支持到位之后。
+fn run_process_subsystem_self_check(
+    mapper: &OffsetPageTable,
+    physical_memory_offset: VirtAddr,
+    frame_allocator: &mut BootInfoFrameAllocator,
+) {
+    let elf_bytes = build_self_check_elf();
+    match unsafe { Process::from_elf(&elf_bytes, mapper, physical_memory_offset, frame_allocator) }
+    {
+        Ok(process) => println!(
+            "process subsystem self-check: loaded {:?}, entry point at {:?}",
+            process.id, process.entry_point
+        ),
+        Err(e) => println!("process subsystem self-check failed: {}", e),
+    }
+}
+
+/// 手工拼出一个仅用于自检的、最小的静态链接ELF64可执行文件
+///
+/// 只有一个ELF头和一个`PT_LOAD`段，段内容是一条单独的`hlt`指令——内容本身
+/// 无关紧要，因为`run_process_subsystem_self_check`只验证加载路径，并不
+/// 会真的跳过去执行它。
+fn build_self_check_elf() -> Vec<u8> {
+    const ENTRY_VADDR: u64 = 0x0000_4000_0000_0000;
+    const EHDR_SIZE: u64 = 64;
+    const PHDR_SIZE: u64 = 56;
+    const CODE: &[u8] = &[0xf4]; // hlt
+
+    let mut elf = Vec::new();
+
+    // e_ident: 魔数、64位、小端序、当前版本、System V ABI，其余留空
+    elf.extend_from_slice(&[0x7f, b'E', b'L', b'F']);
+    elf.push(2); // ELFCLASS64
+    elf.push(1); // ELFDATA2LSB
+    elf.push(1); // EV_CURRENT
+    elf.push(0); // ELFOSABI_SYSV
+    elf.extend_from_slice(&[0u8; 8]); // 填充字节
+
+    elf.extend_from_slice(&2u16.to_le_bytes()); // e_type = ET_EXEC
+    elf.extend_from_slice(&0x3eu16.to_le_bytes()); // e_machine = EM_X86_64
+    elf.extend_from_slice(&1u32.to_le_bytes()); // e_version
+    elf.extend_from_slice(&ENTRY_VADDR.to_le_bytes()); // e_entry
+    elf.extend_from_slice(&EHDR_SIZE.to_le_bytes()); // e_phoff：紧跟在ELF头之后
+    elf.extend_from_slice(&0u64.to_le_bytes()); // e_shoff：不携带节表
+    elf.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+    elf.extend_from_slice(&(EHDR_SIZE as u16).to_le_bytes()); // e_ehsize
+    elf.extend_from_slice(&(PHDR_SIZE as u16).to_le_bytes()); // e_phentsize
+    elf.extend_from_slice(&1u16.to_le_bytes()); // e_phnum：唯一一个程序头
+    elf.extend_from_slice(&0u16.to_le_bytes()); // e_shentsize
+    elf.extend_from_slice(&0u16.to_le_bytes()); // e_shnum
+    elf.extend_from_slice(&0u16.to_le_bytes()); // e_shstrndx
+    debug_assert_eq!(elf.len() as u64, EHDR_SIZE);
+
+    let code_file_offset = EHDR_SIZE + PHDR_SIZE;
+    elf.extend_from_slice(&1u32.to_le_bytes()); // p_type = PT_LOAD
+    elf.extend_from_slice(&5u32.to_le_bytes()); // p_flags = PF_R | PF_X
+    elf.extend_from_slice(&code_file_offset.to_le_bytes()); // p_offset
+    elf.extend_from_slice(&ENTRY_VADDR.to_le_bytes()); // p_vaddr
+    elf.extend_from_slice(&ENTRY_VADDR.to_le_bytes()); // p_paddr
+    elf.extend_from_slice(&(CODE.len() as u64).to_le_bytes()); // p_filesz
+    elf.extend_from_slice(&(CODE.len() as u64).to_le_bytes()); // p_memsz
+    elf.extend_from_slice(&4096u64.to_le_bytes()); // p_align
+    debug_assert_eq!(elf.len() as u64, code_file_offset);
+
+    elf.extend_from_slice(CODE);
+    elf
+}
+
 
 /// This function is called on panic.
 #[cfg(not(test))]
@@ -2,49 +2,113 @@ use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
 use x86_64::{
     registers::control::Cr3,
     structures::paging::{
-        page_table::FrameError, FrameAllocator, Mapper, OffsetPageTable, Page, PageTable,
-        PhysFrame, Size4KiB,
+        page_table::FrameError, FrameAllocator, FrameDeallocator, Mapper, OffsetPageTable, Page,
+        PageTable, PhysFrame, Size4KiB,
     },
     PhysAddr, VirtAddr,
 };
 
+/// 一个存储于已释放帧内部的链表节点
+///
+/// 通过`physical_memory_offset`把帧的物理地址映射成可以读写的虚拟地址，
+/// 就像堆分配器（`allocator::linked_list`/`allocator::fixed_size_block`）
+/// 把`ListNode`写进被释放的内存块里一样，这样空闲帧链表就不需要任何额外
+/// 的存储——而这一点很重要，因为该分配器在堆初始化之前就要开始工作（用于
+/// 映射堆本身），此时`alloc::vec::Vec`还不可用。
+struct FreeFrameNode {
+    next: Option<&'static mut FreeFrameNode>,
+}
+
 /// 一个从bootloader内存映射中返回可用帧的帧分配器
+///
+/// 为了避免每次分配都重新展平并扫描整张内存映射，分配器用一个游标
+/// （`region_index` + `next_frame_addr`）记住上次扫描到的位置，并在
+/// `deallocate_frame`归还的帧上优先复用，使分配和释放都摊还为O(1)。
 pub struct BootInfoFrameAllocator {
     memory_map: &'static MemoryMap,
-    next: usize,
+    physical_memory_offset: VirtAddr,
+    region_index: usize,
+    next_frame_addr: Option<u64>,
+    free_list: Option<&'static mut FreeFrameNode>,
 }
 
 impl BootInfoFrameAllocator {
     /// 从传入的内存映射中创建帧分配器
     ///
-    /// 该函数为非安全，因为调用者必须确保传入的内存映射是有效的。
-    /// 主要要求是其中所有标记为`USABLE`的帧实际上都未被使用。
-    pub unsafe fn init(memory_map: &'static MemoryMap) -> Self {
+    /// 该函数为非安全，因为调用者必须确保传入的内存映射是有效的，其中
+    /// 所有标记为`USABLE`的帧实际上都未被使用；并且`physical_memory_offset`
+    /// 处确实映射着完整的物理内存（`deallocate_frame`需要通过它读写被
+    /// 归还的帧，以维护空闲帧链表）。
+    pub unsafe fn init(memory_map: &'static MemoryMap, physical_memory_offset: VirtAddr) -> Self {
         BootInfoFrameAllocator {
             memory_map,
-            next: 0,
+            physical_memory_offset,
+            region_index: 0,
+            next_frame_addr: None,
+            free_list: None,
         }
     }
 
-    /// 返回内存映射中可用帧的迭代器
-    fn usable_frames(&self) -> impl Iterator<Item = PhysFrame> {
+    /// 返回内存映射中可用区域的地址范围迭代器
+    fn usable_regions(&self) -> impl Iterator<Item = core::ops::Range<u64>> {
         // 获取内存映射中的可用区域
         let regions = self.memory_map.iter();
         let usable_regions = regions.filter(|r| r.region_type == MemoryRegionType::Usable);
         // 将各区域化为其地址范围
-        let addr_ranges = usable_regions.map(|r| r.range.start_addr()..r.range.end_addr());
-        // 将这些帧的起始地址化为迭代器
-        let frame_addresses = addr_ranges.flat_map(|r| r.step_by(4096));
-        // 使用这些起始地址创建`PhysFrame`类型
-        frame_addresses.map(|addr| PhysFrame::containing_address(PhysAddr::new(addr)))
+        usable_regions.map(|r| r.range.start_addr()..r.range.end_addr())
+    }
+
+    /// 沿着游标取出下一个尚未分配过的帧，并把游标推进到下一个候选地址
+    ///
+    /// 每次调用只需跳过`region_index`之前的区域（区域数量很少），而不必像
+    /// 之前那样展平并扫描全部可用帧。
+    fn next_mapped_frame(&mut self) -> Option<PhysFrame> {
+        loop {
+            let region = self.usable_regions().nth(self.region_index)?;
+            let addr = *self.next_frame_addr.get_or_insert(region.start);
+
+            if addr >= region.end {
+                // 当前区域已耗尽，前进到下一个区域重新开始
+                self.region_index += 1;
+                self.next_frame_addr = None;
+                continue;
+            }
+
+            self.next_frame_addr = Some(addr + 4096);
+            return Some(PhysFrame::containing_address(PhysAddr::new(addr)));
+        }
     }
 }
 
 unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
     fn allocate_frame(&mut self) -> Option<PhysFrame> {
-        let frame = self.usable_frames().nth(self.next);
-        self.next += 1;
-        frame
+        // 优先复用被释放的帧，其次才向内存映射请求一个新的帧
+        if let Some(node) = self.free_list.take() {
+            let node_virt_addr = node as *const FreeFrameNode as u64;
+            self.free_list = node.next.take();
+            let phys_addr = node_virt_addr - self.physical_memory_offset.as_u64();
+            return Some(PhysFrame::containing_address(PhysAddr::new(phys_addr)));
+        }
+        self.next_mapped_frame()
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for BootInfoFrameAllocator {
+    /// 将帧归还给分配器，使其可以被后续的`allocate_frame`重新分配出去
+    ///
+    /// 归还的帧没有任何容量上限：把一个`FreeFrameNode`写进帧本身（通过
+    /// `physical_memory_offset`访问），再把它接到空闲链表头部，和堆分配
+    /// 器复用被释放内存的方式完全一样。
+    ///
+    /// 该方法为非安全，因为调用者必须保证该帧此后不再被以任何方式使用，
+    /// 并且确实是先前由这个分配器分配出去的，否则可能导致帧被重复分配。
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame) {
+        let virt = self.physical_memory_offset + frame.start_address().as_u64();
+        let node_ptr: *mut FreeFrameNode = virt.as_mut_ptr();
+        node_ptr.write(FreeFrameNode {
+            next: self.free_list.take(),
+        });
+        self.free_list = Some(&mut *node_ptr);
     }
 }
 
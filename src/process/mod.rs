@@ -0,0 +1,266 @@
+//! 用户态进程子系统
+//!
+//! 在`memory`模块提供的`OffsetPageTable`/`BootInfoFrameAllocator`之上，
+//! 解析静态链接的ELF可执行文件，把它的`PT_LOAD`段映射进一个全新的地址
+//! 空间，并以特权级3（用户态）运行它，而不再局限于硬编码的`example_task`。
+
+use core::ptr;
+
+use x86_64::{
+    registers::control::{Cr3, Cr3Flags},
+    structures::paging::{
+        FrameAllocator, Mapper, OffsetPageTable, Page, PageSize, PageTable,
+        PageTableFlags as Flags, PhysFrame, Size4KiB,
+    },
+    VirtAddr,
+};
+use xmas_elf::{
+    program::{ProgramHeader, Type},
+    ElfFile,
+};
+
+use crate::memory::BootInfoFrameAllocator;
+
+/// 用户态栈的大小
+const USER_STACK_SIZE: u64 = 64 * 1024; // 64 KiB
+/// 用户态栈顶的固定虚拟地址（栈向低地址增长）
+const USER_STACK_TOP: u64 = 0x_5555_5555_0000;
+
+/// 用户态代码段/数据段选择子
+///
+/// 这两个选择子假定GDT的第3、4项分别是用户态代码段、数据段描述符（RPL
+/// 为3）；`spawn_user`用它们构造落入ring 3所需的`iretq`栈帧。
+const USER_CODE_SELECTOR: u64 = 0x1B;
+const USER_DATA_SELECTOR: u64 = 0x23;
+
+/// 进程的唯一标识符
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessId(u64);
+
+impl ProcessId {
+    fn new() -> Self {
+        use core::sync::atomic::{AtomicU64, Ordering};
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        ProcessId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// 一个已加载、随时可以运行的用户态进程
+pub struct Process {
+    pub id: ProcessId,
+    pub page_table_frame: PhysFrame,
+    pub entry_point: VirtAddr,
+    pub stack_top: VirtAddr,
+}
+
+impl Process {
+    /// 解析`elf_bytes`中的静态链接ELF可执行文件，为其创建一个独立的地址
+    /// 空间，把所有`PT_LOAD`段映射进去并拷贝文件内容（`.bss`尾部清零），
+    /// 再映射好用户态栈。
+    ///
+    /// `kernel_page_table`与`physical_memory_offset`用于把内核当前页表的
+    /// 高半区表项克隆进新地址空间，使其中的内核映射在切换`CR3`之后依旧可
+    /// 被寻址（陷入、系统调用处理都需要运行在内核的映射之下）。低半区的表项
+    /// 不会被克隆，例如位于`HEAP_START`的内核堆在新地址空间里没有映射。
+    ///
+    /// 该函数为非安全，因为调用者必须保证`elf_bytes`是一个合法的、静态
+    /// 链接的ELF可执行文件，且其程序头声明的虚拟地址范围不会与内核本身
+    /// 或其他已有映射相冲突。
+    pub unsafe fn from_elf(
+        elf_bytes: &[u8],
+        kernel_page_table: &OffsetPageTable,
+        physical_memory_offset: VirtAddr,
+        frame_allocator: &mut BootInfoFrameAllocator,
+    ) -> Result<Process, &'static str> {
+        let elf = ElfFile::new(elf_bytes).map_err(|_| "malformed ELF file")?;
+
+        let page_table_frame = frame_allocator
+            .allocate_frame()
+            .ok_or("no frames available for process page table")?;
+        let new_table = clone_kernel_page_table(
+            kernel_page_table.level_4_table(),
+            page_table_frame,
+            physical_memory_offset,
+        );
+        let mut mapper = OffsetPageTable::new(new_table, physical_memory_offset);
+
+        for program_header in elf.program_iter() {
+            if program_header.get_type() == Ok(Type::Load) {
+                map_load_segment(
+                    &elf,
+                    program_header,
+                    &mut mapper,
+                    physical_memory_offset,
+                    frame_allocator,
+                )?;
+            }
+        }
+
+        map_user_stack(&mut mapper, frame_allocator)?;
+
+        Ok(Process {
+            id: ProcessId::new(),
+            page_table_frame,
+            entry_point: VirtAddr::new(elf.header.pt2.entry_point()),
+            stack_top: VirtAddr::new(USER_STACK_TOP),
+        })
+    }
+}
+
+/// 创建一份新的4级页表，并从内核当前页表中克隆高半区（内核空间）表项，
+/// 使得切换到该页表之后，映射在高半区的内核代码和数据依旧可被访问；
+/// 低半区（0~255号）表项保持为空。
+unsafe fn clone_kernel_page_table(
+    kernel_table: &PageTable,
+    new_table_frame: PhysFrame,
+    physical_memory_offset: VirtAddr,
+) -> &'static mut PageTable {
+    let virt = physical_memory_offset + new_table_frame.start_address().as_u64();
+    let new_table_ptr: *mut PageTable = virt.as_mut_ptr();
+    new_table_ptr.write(PageTable::new());
+    let new_table = &mut *new_table_ptr;
+
+    // x86_64的4级页表中，256号及以上的表项覆盖高半区(0xffff_8000_0000_0000起)，
+    // 按惯例用来映射内核本身；用户地址空间只占据0~255号表项。
+    for i in 256..512 {
+        new_table[i] = kernel_table[i].clone();
+    }
+
+    new_table
+}
+
+/// 将一个`PT_LOAD`段映射进新的地址空间，拷贝文件内容并清零`.bss`尾部
+///
+/// `new_table`此时还不是激活的页表（`CR3`依旧指向内核自己的页表，只有
+/// `spawn_user`切换之后才会指向它），所以不能像直接运行在目标地址空间里
+/// 那样通过`page.start_address()`读写——那是*目标进程*的虚拟地址，在当前
+/// 激活的（内核）页表下根本没有映射，解引用会触发页错误。和
+/// `clone_kernel_page_table`一样，这里必须经由`physical_memory_offset`
+/// 把刚分配的物理帧映射到一个内核当下就能访问的虚拟地址，再写入。
+fn map_load_segment(
+    elf: &ElfFile,
+    program_header: ProgramHeader,
+    mapper: &mut OffsetPageTable,
+    physical_memory_offset: VirtAddr,
+    frame_allocator: &mut BootInfoFrameAllocator,
+) -> Result<(), &'static str> {
+    let segment_flags = program_header.flags();
+    let mut flags = Flags::PRESENT | Flags::USER_ACCESSIBLE;
+    if segment_flags.is_write() {
+        flags |= Flags::WRITABLE;
+    }
+    if !segment_flags.is_execute() {
+        flags |= Flags::NO_EXECUTE;
+    }
+
+    let virt_start = VirtAddr::new(program_header.virtual_addr());
+    let mem_size = program_header.mem_size();
+    let file_size = program_header.file_size();
+    let file_offset = program_header.offset();
+    let file_data = &elf.input[file_offset as usize..(file_offset + file_size) as usize];
+
+    let start_page = Page::<Size4KiB>::containing_address(virt_start);
+    let end_page = Page::<Size4KiB>::containing_address(virt_start + mem_size.max(1) - 1u64);
+
+    let seg_start = virt_start.as_u64();
+    let seg_file_end = seg_start + file_size;
+
+    for page in Page::range_inclusive(start_page, end_page) {
+        let frame = frame_allocator
+            .allocate_frame()
+            .ok_or("no frames available for PT_LOAD segment")?;
+        unsafe {
+            mapper
+                .map_to(page, frame, flags, frame_allocator)
+                .map_err(|_| "failed to map PT_LOAD segment")?
+                .flush();
+        }
+
+        let page_start = page.start_address().as_u64();
+        let page_end = page_start + Size4KiB::SIZE;
+
+        // 通过`physical_memory_offset`把这一页对应的物理帧映射到内核当下
+        // （目标页表尚未激活）就能访问的虚拟地址，所有读写都经由它进行。
+        let frame_start_virt = physical_memory_offset + frame.start_address().as_u64();
+
+        // 先把整页清零（这就处理好了`.bss`：file_size之后、mem_size之内的
+        // 部分永远不会被下面的拷贝覆盖，天然保持为零）。
+        let dst_page = frame_start_virt.as_mut_ptr::<u8>();
+        unsafe { ptr::write_bytes(dst_page, 0, Size4KiB::SIZE as usize) };
+
+        // `virt_start`未必按页对齐：段数据在这一页内的起止地址是
+        // [seg_start, seg_file_end)与[page_start, page_end)的交集，而不是
+        // 总能从`page_start`开始——否则对于第一页之后的每一页，拷贝目标都
+        // 会被`virt_start`落在页内的偏移量错误地整体偏移。
+        let copy_dst_start = page_start.max(seg_start);
+        let copy_dst_end = page_end.min(seg_file_end);
+
+        if copy_dst_end > copy_dst_start {
+            let copy_len = (copy_dst_end - copy_dst_start) as usize;
+            let file_offset_in_segment = (copy_dst_start - seg_start) as usize;
+            let page_offset = (copy_dst_start - page_start) as usize;
+            unsafe {
+                let src = file_data[file_offset_in_segment..file_offset_in_segment + copy_len]
+                    .as_ptr();
+                let dst = frame_start_virt.as_mut_ptr::<u8>().add(page_offset);
+                ptr::copy_nonoverlapping(src, dst, copy_len);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 为进程映射一段用户态可写、不可执行的栈
+fn map_user_stack(
+    mapper: &mut OffsetPageTable,
+    frame_allocator: &mut BootInfoFrameAllocator,
+) -> Result<(), &'static str> {
+    let stack_end = VirtAddr::new(USER_STACK_TOP);
+    let stack_start = stack_end - USER_STACK_SIZE;
+    let start_page = Page::<Size4KiB>::containing_address(stack_start);
+    let end_page = Page::<Size4KiB>::containing_address(stack_end - 1u64);
+    let flags = Flags::PRESENT | Flags::WRITABLE | Flags::USER_ACCESSIBLE | Flags::NO_EXECUTE;
+
+    for page in Page::range_inclusive(start_page, end_page) {
+        let frame = frame_allocator
+            .allocate_frame()
+            .ok_or("no frames available for user stack")?;
+        unsafe {
+            mapper
+                .map_to(page, frame, flags, frame_allocator)
+                .map_err(|_| "failed to map user stack")?
+                .flush();
+        }
+    }
+
+    Ok(())
+}
+
+/// 切换到进程自己的地址空间并跳转到其入口点，以特权级3（用户态）运行它
+///
+/// 该函数为非安全，因为它会直接切换`CR3`并执行特权级切换；调用者必须
+/// 保证`process`是一个已经通过`Process::from_elf`正确构建、尚未销毁的
+/// 进程，并且GDT中已经安装了用户态代码段/数据段选择子。
+pub unsafe fn spawn_user(process: &Process) -> ! {
+    Cr3::write(process.page_table_frame, Cr3Flags::empty());
+
+    let entry_point = process.entry_point.as_u64();
+    let stack_top = process.stack_top.as_u64();
+
+    // 手工构造`iretq`所需的栈帧（SS、RSP、RFLAGS、CS、RIP，按出栈顺序反向压入），
+    // 从ring 0落入ring 3，跳转到用户态的入口点。
+    core::arch::asm!(
+        "push {data_sel}",
+        "push {stack_top}",
+        "pushfq",
+        "push {code_sel}",
+        "push {entry}",
+        "iretq",
+        data_sel = in(reg) USER_DATA_SELECTOR,
+        stack_top = in(reg) stack_top,
+        code_sel = in(reg) USER_CODE_SELECTOR,
+        entry = in(reg) entry_point,
+        options(noreturn),
+    );
+}
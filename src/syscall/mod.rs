@@ -0,0 +1,191 @@
+//! 系统调用子系统
+//!
+//! 在`process`子系统之上，为用户态代码提供一个最小的系统调用ABI：用户
+//! 程序通过软中断`int 0x80`陷入内核，在寄存器中传递调用号与参数，由
+//! `dispatch`分发到具体的处理函数并把结果带回用户态。
+
+use core::arch::asm;
+
+use x86_64::{structures::idt::InterruptDescriptorTable, PrivilegeLevel, VirtAddr};
+
+use crate::{exit_qemu, println, serial_println, task::executor, QemuExitCode};
+
+/// 系统调用所使用的软中断向量号
+pub const SYSCALL_INTERRUPT_ID: u8 = 0x80;
+
+/// 受支持的系统调用号
+pub mod number {
+    pub const WRITE: usize = 0;
+    pub const EXIT: usize = 1;
+    pub const YIELD: usize = 2;
+}
+
+/// 向IDT中安装系统调用的中断门
+///
+/// `syscall_entry`是一个裸函数，不符合`extern "x86-interrupt" fn(..)`的
+/// 类型（它自己负责保存寄存器和`iretq`），所以用`set_handler_addr`直接
+/// 安装它的地址，而不是类型化的`set_handler_fn`。中断门默认的DPL是0，
+/// 必须显式调`set_privilege_level(Ring3)`把它降到3，用户态的`int 0x80`
+/// 才不会被当成特权级违规而是触发`#GP`。
+pub fn register(idt: &mut InterruptDescriptorTable) {
+    idt[SYSCALL_INTERRUPT_ID as usize]
+        .set_handler_addr(VirtAddr::new(syscall_entry as u64))
+        .set_privilege_level(PrivilegeLevel::Ring3);
+}
+
+/// `int 0x80`的陷入入口
+///
+/// 这里必须用`#[naked]`裸函数加手写内联汇编，而不是
+/// `extern "x86-interrupt" fn`：后者的调用约定由编译器生成序言/尾声，只
+/// 保存它自己用到的寄存器，根本不会把用户态传入的`rax`（调用号）、
+/// `rdi`/`rsi`/`rdx`（参数）暴露给函数体。这段裸函数在编译器插入任何
+/// 序言之前就把这几个寄存器（以及`dispatch`调用会破坏的其它易失寄存器）
+/// 压栈保存，按SysV调用约定重新摆放成`dispatch(num, a0, a1, a2)`的参数，
+/// 调用它，把返回值写回保存的`rax`槽位，再逐一弹出寄存器并`iretq`，让
+/// 返回值通过`rax`带回用户态。
+#[naked]
+pub extern "C" fn syscall_entry() {
+    unsafe {
+        asm!(
+            // 保存调用号/参数寄存器，以及`call`会按SysV约定破坏的其余
+            // 易失寄存器；压栈顺序固定之后，下面用到的`[rsp + N]`偏移量
+            // 才是确定的。
+            "push rax",
+            "push rcx",
+            "push rdx",
+            "push rsi",
+            "push rdi",
+            "push r8",
+            "push r9",
+            "push r10",
+            "push r11",
+            // 陷入时：rax=调用号，rdi=a0，rsi=a1，rdx=a2。把它们从已保存的
+            // 栈槽位读出，按`dispatch_trampoline(num, a0, a1, a2)`的SysV
+            // 参数顺序重新摆进rdi/rsi/rdx/rcx（用尚未写回的寄存器做中转，
+            // 它们的原始值已经在栈上了，稍后会从栈恢复，不受影响）。
+            "mov rcx, [rsp + 48]", // 原始rdx(a2) -> 第4个参数
+            "mov r11, [rsp + 40]", // 原始rsi(a1) -> 暂存
+            "mov rdx, r11",        //              -> 第3个参数
+            "mov r11, [rsp + 32]", // 原始rdi(a0) -> 暂存
+            "mov rsi, r11",        //              -> 第2个参数
+            "mov rdi, [rsp + 64]", // 原始rax(调用号) -> 第1个参数
+            // SysV要求`call`发生时栈是16字节对齐的；CPU陷入时（特权级发生
+            // 变化）压入的5个控制寄存器（40字节）加上这里压入的9个寄存器
+            // （72字节）合计112字节，已经是16的倍数，不需要再填充——多减的
+            // 8字节反而会让`call`发生时栈偏出对齐，'dispatch'内部一旦有
+            // 对齐的XMM存取就会崩溃。
+            "call {dispatch}",
+            // `dispatch_trampoline`的返回值在rax中：写回保存的rax槽位，
+            // 这样下面的`pop rax`会把结果带回用户态。
+            "mov [rsp + 64], rax",
+            "pop r11",
+            "pop r10",
+            "pop r9",
+            "pop r8",
+            "pop rdi",
+            "pop rsi",
+            "pop rdx",
+            "pop rcx",
+            "pop rax",
+            "iretq",
+            dispatch = sym dispatch_trampoline,
+            options(noreturn),
+        );
+    }
+}
+
+/// 供`syscall_entry`调用的普通（非裸）分发入口
+///
+/// 签名遵循SysV调用约定（参数依次在rdi/rsi/rdx/rcx中），与`syscall_entry`
+/// 里重新摆放寄存器的顺序一一对应。
+extern "C" fn dispatch_trampoline(num: usize, a0: usize, a1: usize, a2: usize) -> isize {
+    dispatch(num, a0, a1, a2)
+}
+
+/// 系统调用的核心分发逻辑
+///
+/// 遵循“负数表示错误”的惯例；成功时返回一个非负的结果。
+pub fn dispatch(num: usize, a0: usize, a1: usize, a2: usize) -> isize {
+    match num {
+        number::WRITE => sys_write(a0, a1),
+        number::EXIT => sys_exit(a0),
+        number::YIELD => sys_yield(),
+        _ => -1,
+    }
+}
+
+/// 用户地址空间的规范地址上界
+///
+/// 对应4级页表0~255号表项所覆盖的范围（见`process::clone_kernel_page_table`
+/// 对256号及以上表项的说明）；256号往上是内核的高半区，任何用户系统调用
+/// 都不应该能以此为跳板让内核替它读写那片地址。
+const USER_ADDR_SPACE_END: usize = 0x0000_8000_0000_0000;
+
+/// 校验`[buf, buf + len)`是否完全落在用户地址空间内
+///
+/// 这只是一个下限检查：它排除了指向内核高半区或溢出回绕的指针，但并不
+/// 确认这段范围在*当前*进程的页表里确实被映射、确实可读——要做到这一点
+/// 需要遍历调用进程自己的页表项，而这需要`dispatch`知道“当前是哪个进程”
+/// （即一张进程表），这在这个最小系统调用ABI里还不存在。即便如此，`buf`
+/// 未被正确映射的情况仍然会在下面的`from_raw_parts`解引用时触发页错误,
+/// 而不会越权访问到内核本身的数据。
+fn validate_user_range(buf: usize, len: usize) -> Result<(), ()> {
+    let end = buf.checked_add(len).ok_or(())?;
+    if end > USER_ADDR_SPACE_END {
+        return Err(());
+    }
+    Ok(())
+}
+
+/// `write`：把`buf`指向的`len`字节以UTF-8文本写到控制台
+///
+/// 同时走VGA文本缓冲区（`println!`）和串口（`serial_println!`）两条既有
+/// 输出路径。
+fn sys_write(buf: usize, len: usize) -> isize {
+    if validate_user_range(buf, len).is_err() {
+        return -1;
+    }
+
+    let bytes = unsafe { core::slice::from_raw_parts(buf as *const u8, len) };
+    match core::str::from_utf8(bytes) {
+        Ok(s) => {
+            println!("{}", s);
+            serial_println!("{}", s);
+            len as isize
+        }
+        Err(_) => -1,
+    }
+}
+
+/// `exit`：终止当前进程
+///
+/// 这个最小实现还没有进程表和多进程调度，所以`exit`直接把整个内核关停；
+/// 一旦有了真正的进程表，这里应改为只回收调用进程的资源，再把控制权交
+/// 还给`Executor`去调度下一个任务。
+fn sys_exit(status: usize) -> isize {
+    let code = if status == 0 {
+        QemuExitCode::Success
+    } else {
+        QemuExitCode::Failed
+    };
+    serial_println!("process exited with status {}", status);
+    exit_qemu(code);
+    // `exit_qemu`只是向QEMU的isa-debug-exit设备写入退出码，并不保证立即
+    // 终止当前执行流，所以和`should_panic`测试入口一样，用一个停机循环
+    // 兜底。
+    loop {}
+}
+
+/// `yield`：把控制权交还给协作式的`Executor`
+///
+/// 唤醒当前正在被`Executor`轮询的任务的`Waker`，使其重新排入任务队列。
+/// 由于还没有实现跨陷入的寄存器/栈上下文保存与恢复，这里无法真正挂起
+/// 用户进程的执行再之后恢复它；这一步完成的是调度层面的集成（`yield`
+/// 会被`Executor`看到并重新调度），完整的进程挂起/恢复留给后续的上下文
+/// 切换实现。
+fn sys_yield() -> isize {
+    if let Some(waker) = executor::current_waker() {
+        waker.wake();
+    }
+    0
+}
@@ -0,0 +1,126 @@
+use alloc::{collections::BTreeMap, sync::Arc, task::Wake};
+use core::task::{Context, Poll, Waker};
+
+use crossbeam_queue::ArrayQueue;
+use spin::Mutex;
+
+use super::{Task, TaskId};
+
+/// 当前正在被轮询的任务的`Waker`
+///
+/// 该内核假设运行在单核上，所以用一个静态单元保存“当前任务”的`Waker`即
+/// 可：`syscall`模块处理`yield`系统调用时读取它并唤醒，把当前正在被轮询
+/// 的任务的ID重新放回任务队列。陷入内核的用户进程本身不是`Executor`中的
+/// 任务，不会因此被挂起或重新调度。
+static CURRENT_WAKER: Mutex<Option<Waker>> = Mutex::new(None);
+
+/// 返回当前正在被`Executor`轮询的任务的`Waker`（如果存在）
+pub fn current_waker() -> Option<Waker> {
+    CURRENT_WAKER.lock().clone()
+}
+
+/// 一个简单的协作式异步任务调度器
+///
+/// 与`SimpleExecutor`不同，它为每个任务缓存一个只在该任务被重新唤醒时才
+/// 把其ID放回`task_queue`的`Waker`，并在队列为空时通过`hlt`让CPU休眠，
+/// 避免空转。
+pub struct Executor {
+    tasks: BTreeMap<TaskId, Task>,
+    task_queue: Arc<ArrayQueue<TaskId>>,
+    waker_cache: BTreeMap<TaskId, Waker>,
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Executor {
+            tasks: BTreeMap::new(),
+            task_queue: Arc::new(ArrayQueue::new(100)),
+            waker_cache: BTreeMap::new(),
+        }
+    }
+
+    pub fn spawn(&mut self, task: Task) {
+        let task_id = task.id;
+        if self.tasks.insert(task.id, task).is_some() {
+            panic!("task with same ID already in tasks");
+        }
+        self.task_queue.push(task_id).expect("task_queue full");
+    }
+
+    fn run_ready_tasks(&mut self) {
+        let Self {
+            tasks,
+            task_queue,
+            waker_cache,
+        } = self;
+
+        while let Some(task_id) = task_queue.pop() {
+            let task = match tasks.get_mut(&task_id) {
+                Some(task) => task,
+                None => continue, // 该任务已经结束
+            };
+            let waker = waker_cache
+                .entry(task_id)
+                .or_insert_with(|| TaskWaker::new(task_id, task_queue.clone()));
+
+            *CURRENT_WAKER.lock() = Some(waker.clone());
+            let mut context = Context::from_waker(waker);
+            let poll_result = task.poll(&mut context);
+            *CURRENT_WAKER.lock() = None;
+
+            match poll_result {
+                Poll::Ready(()) => {
+                    tasks.remove(&task_id);
+                    waker_cache.remove(&task_id);
+                }
+                Poll::Pending => {}
+            }
+        }
+    }
+
+    pub fn run(&mut self) -> ! {
+        loop {
+            self.run_ready_tasks();
+            self.sleep_if_idle();
+        }
+    }
+
+    fn sleep_if_idle(&self) {
+        use x86_64::instructions::interrupts::{self, enable_and_hlt};
+
+        interrupts::disable();
+        if self.task_queue.is_empty() {
+            enable_and_hlt();
+        } else {
+            interrupts::enable();
+        }
+    }
+}
+
+struct TaskWaker {
+    task_id: TaskId,
+    task_queue: Arc<ArrayQueue<TaskId>>,
+}
+
+impl TaskWaker {
+    fn new(task_id: TaskId, task_queue: Arc<ArrayQueue<TaskId>>) -> Waker {
+        Waker::from(Arc::new(TaskWaker {
+            task_id,
+            task_queue,
+        }))
+    }
+
+    fn wake_task(&self) {
+        self.task_queue.push(self.task_id).expect("task_queue full");
+    }
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_task();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wake_task();
+    }
+}
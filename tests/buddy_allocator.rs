@@ -0,0 +1,116 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![reexport_test_harness_main = "test_main"]
+#![test_runner(test_runner)]
+
+use alloc::{boxed::Box, vec::Vec};
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use os_rust_demo::allocator::buddy::BuddyAllocator;
+use os_rust_demo::allocator::{bump::Locked, HEAP_START, HEAP_SIZE};
+use os_rust_demo::test_runner;
+use os_rust_demo::{
+    init,
+    memory::{self, BootInfoFrameAllocator},
+    test_panic_handler,
+};
+use x86_64::{
+    structures::paging::{Mapper, Page, PageTableFlags},
+    VirtAddr,
+};
+
+extern crate alloc;
+
+entry_point!(main);
+
+// 这个测试二进制专门用来验证`BuddyAllocator`本身，所以它不经由
+// `allocator::init_heap`（那个函数固定初始化的是`allocator::mod`里选中的
+// `FixedSizeBlockAllocator`），而是把`BuddyAllocator`装成这个测试自己的
+// `#[global_allocator]`，复用同一段堆地址范围，自己映射页面、自己调用
+// `BuddyAllocator::init`。
+#[global_allocator]
+static ALLOCATOR: Locked<BuddyAllocator> = Locked::new(BuddyAllocator::new());
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    init();
+    let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { memory::init(phys_mem_offset) };
+    let mut frame_allocator =
+        unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map, phys_mem_offset) };
+
+    let heap_start = VirtAddr::new(HEAP_START as u64);
+    let heap_end = heap_start + HEAP_SIZE - 1u64;
+    let page_range = Page::range_inclusive(
+        Page::containing_address(heap_start),
+        Page::containing_address(heap_end),
+    );
+    for page in page_range {
+        let frame = frame_allocator
+            .allocate_frame()
+            .expect("no frames available for heap");
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+        unsafe {
+            mapper
+                .map_to(page, frame, flags, &mut frame_allocator)
+                .expect("heap mapping failed")
+                .flush()
+        };
+    }
+    unsafe { ALLOCATOR.lock().init(HEAP_START, HEAP_SIZE) };
+
+    test_main();
+    loop {}
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    test_panic_handler(info)
+}
+
+#[test_case]
+fn simple_allocation() {
+    let heap_value_1 = Box::new(4);
+    let heap_value_2 = Box::new(5);
+    assert_eq!(*heap_value_1, 4);
+    assert_eq!(*heap_value_2, 5);
+}
+
+#[test_case]
+fn large_vec() {
+    let n = 1000;
+    let mut vec = Vec::new();
+    for i in 0..n {
+        vec.push(i);
+    }
+    assert_eq!(vec.iter().sum::<u64>(), (n - 1) * n / 2);
+}
+
+#[test_case]
+fn many_boxes() {
+    // 每个`Box`都在下一次分配之前被释放：`HEAP_SIZE`次分配累计需要的字节
+    // 数远超堆的容量，所以只有`deallocate`把块放回空闲链表供下一次分配
+    // 复用，这`HEAP_SIZE`个`usize`才能全部分配成功。
+    for i in 0..HEAP_SIZE {
+        let x = Box::new(i);
+        assert_eq!(*x, i);
+    }
+}
+
+#[test_case]
+fn freed_blocks_are_reused() {
+    // 先分配再释放一大批块，再重新分配同样多，并检查第二轮分配出的值。
+    // 两轮请求的块大小完全相同，所以这里检查的是释放的块能被再次分配；
+    // 伙伴块是否被合并回更高阶并不在这个测试的检查范围内。
+    {
+        let mut values = Vec::new();
+        for i in 0..1000u64 {
+            values.push(Box::new(i));
+        }
+    }
+    let mut values = Vec::new();
+    for i in 0..1000u64 {
+        values.push(Box::new(i));
+    }
+    assert_eq!(values.iter().map(|b| **b).sum::<u64>(), 1000 * 999 / 2);
+}
@@ -24,7 +24,8 @@ fn main(boot_info: &'static BootInfo) -> ! {
     init();
     let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
     let mut mapper = unsafe { memory::init(phys_mem_offset) };
-    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+    let mut frame_allocator =
+        unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map, phys_mem_offset) };
     allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
     test_main();
     loop {}